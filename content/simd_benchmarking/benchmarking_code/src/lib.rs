@@ -1,27 +1,464 @@
-/// recursivly compute the b-spline basis function for the given index `i`, degree `k`, and knot vector, at the given parameter `x`
+#![feature(portable_simd)]
+use std::ops::{Add, Mul};
+use std::simd::cmp::SimdPartialEq;
+use std::simd::f64x4;
+use std::simd::Select;
+
+/// recursivly compute the b-spline basis function for the given index `i`, degree `k`, and knot vector, at the given parameter `x`.
+/// the half-open test `knots[i] <= x < knots[i+1]` is widened to also accept `x` exactly at the
+/// final knot (evaluated as the limit from the left), and a zero-width knot interval (from a
+/// repeated/clamped knot) contributes 0 instead of dividing by zero
 pub fn basis_activation(i: usize, k: usize, x: f64, knots: &[f64]) -> f64 {
     if k == 0 {
-        if knots[i] <= x && x < knots[i + 1] {
+        let last_knot = knots[knots.len() - 1];
+        let at_final_knot = x == last_knot && knots[i + 1] == last_knot;
+        if knots[i] <= x && (x < knots[i + 1] || at_final_knot) {
             return 1.0;
         } else {
             return 0.0;
         }
     }
-    let left_coefficient = (x - knots[i]) / (knots[i + k] - knots[i]);
+    let left_span = knots[i + k] - knots[i];
+    let left_coefficient = if left_span != 0.0 {
+        (x - knots[i]) / left_span
+    } else {
+        0.0
+    };
     let left_recursion = basis_activation(i, k - 1, x, knots);
 
-    let right_coefficient = (knots[i + k + 1] - x) / (knots[i + k + 1] - knots[i + 1]);
+    let right_span = knots[i + k + 1] - knots[i + 1];
+    let right_coefficient = if right_span != 0.0 {
+        (knots[i + k + 1] - x) / right_span
+    } else {
+        0.0
+    };
     let right_recursion = basis_activation(i + 1, k - 1, x, knots);
 
     let result = left_coefficient * left_recursion + right_coefficient * right_recursion;
     return result;
 }
 
-/// Calculate the value of the B-spline at the given parameter `x`
-pub fn b_spline(x: f64, control_points: &[f64], knots: &[f64], degree: usize) -> f64 {
+/// find the knot span index `mu` such that `knots[mu] <= x < knots[mu + 1]`; an `x` at or past
+/// `knots[control_points_len]` (the end of the domain) maps to the last span,
+/// `control_points_len - 1`, and an `x` below `knots[degree]` maps to span `degree`
+fn find_knot_span(x: f64, degree: usize, control_points_len: usize, knots: &[f64]) -> usize {
+    let last = control_points_len - 1;
+    if x >= knots[last + 1] {
+        return last;
+    }
+    let mut mu = degree;
+    while mu < last && knots[mu + 1] <= x {
+        mu += 1;
+    }
+    mu
+}
+
+/// compute the `degree + 1` non-zero basis functions at `x` in knot span `mu`, via the
+/// triangular Cox-de Boor recurrence, returning `N[r] = basis_activation(mu - degree + r, degree, x, knots)`
+fn nonzero_basis_functions(mu: usize, degree: usize, x: f64, knots: &[f64]) -> Vec<f64> {
+    let mut n = vec![1.0; degree + 1];
+    let mut left = vec![0.0; degree + 1];
+    let mut right = vec![0.0; degree + 1];
+    for j in 1..=degree {
+        left[j] = x - knots[mu + 1 - j];
+        right[j] = knots[mu + j] - x;
+        let mut saved = 0.0;
+        for r in 0..j {
+            let denominator = right[r + 1] + left[j - r];
+            // a zero-width knot interval (repeated/clamped knot) contributes 0, not NaN
+            let temp = if denominator != 0.0 {
+                n[r] / denominator
+            } else {
+                0.0
+            };
+            n[r] = saved + right[r + 1] * temp;
+            saved = left[j - r] * temp;
+        }
+        n[j] = saved;
+    }
+    n
+}
+
+/// build a clamped (open-uniform) knot vector for `num_control_points` control points and the
+/// given `degree`: the first and last knots are each repeated `degree + 1` times, with the
+/// interior knots spaced one apart. Clamping makes the curve interpolate its first and last
+/// control points, which users of `b_spline` generally expect.
+pub fn clamped_knot_vector(num_control_points: usize, degree: usize) -> Vec<f64> {
+    assert!(
+        num_control_points > degree,
+        "need at least degree + 1 control points"
+    );
+    let num_interior = num_control_points - degree - 1;
+    let max = (num_interior + 1) as f64;
+
+    let mut knots = vec![0.0; degree + 1];
+    knots.extend((1..=num_interior).map(|i| i as f64));
+    knots.extend(std::iter::repeat_n(max, degree + 1));
+    knots
+}
+
+/// recursive reference implementation of `b_spline`, kept around as the baseline for
+/// `bench_recursive_method`
+pub fn b_spline_recursive(x: f64, control_points: &[f64], knots: &[f64], degree: usize) -> f64 {
     let mut result = 0.0;
-    for i in 0..control_points.len() {
-        result += control_points[i] * basis_activation(i, degree, x, knots);
+    for (i, &control_point) in control_points.iter().enumerate() {
+        result += control_point * basis_activation(i, degree, x, knots);
     }
-    return result;
-}
\ No newline at end of file
+    result
+}
+
+/// Calculate the value of the B-spline at the given parameter `x`, via De Boor's iterative
+/// evaluation: locate the knot span containing `x`, compute only the `degree + 1` non-zero
+/// basis functions, and combine them with the corresponding control points. This is O(degree^2)
+/// per evaluation, versus the exponential blowup of the naive recursion in `basis_activation`.
+pub fn b_spline<T>(x: f64, control_points: &[T], knots: &[f64], degree: usize) -> T
+where
+    T: Add<Output = T> + Mul<f64, Output = T> + Clone,
+{
+    let mu = find_knot_span(x, degree, control_points.len(), knots);
+    let n = nonzero_basis_functions(mu, degree, x, knots);
+    let mut terms = (0..=degree).map(|r| control_points[mu - degree + r].clone() * n[r]);
+    let mut result = terms.next().expect("degree + 1 >= 1");
+    for term in terms {
+        result = result + term;
+    }
+    result
+}
+
+/// Calculate the value of the rational B-spline (NURBS) at the given parameter `x`, given a
+/// per-control-point `weights` slice: `(sum of w_i * P_i * N_i(x)) / (sum of w_i * N_i(x))`,
+/// reusing the same non-zero basis functions as `b_spline`. Reduces to `b_spline` when all
+/// weights are equal.
+pub fn b_spline_rational<T>(
+    x: f64,
+    control_points: &[T],
+    weights: &[f64],
+    knots: &[f64],
+    degree: usize,
+) -> T
+where
+    T: Add<Output = T> + Mul<f64, Output = T> + Clone,
+{
+    let mu = find_knot_span(x, degree, control_points.len(), knots);
+    let n = nonzero_basis_functions(mu, degree, x, knots);
+
+    let mut denominator = 0.0;
+    let mut terms = (0..=degree).map(|r| {
+        let idx = mu - degree + r;
+        let weighted_basis = weights[idx] * n[r];
+        denominator += weighted_basis;
+        control_points[idx].clone() * weighted_basis
+    });
+    let mut numerator = terms.next().expect("degree + 1 >= 1");
+    for term in terms {
+        numerator = numerator + term;
+    }
+    numerator * (1.0 / denominator)
+}
+
+/// differentiate a degree-`degree` spline once, via the control-point differencing identity:
+/// the derivative is itself a degree-`(degree - 1)` spline with control points
+/// `Q_i = degree * (P_{i+1} - P_i) / (knots[i+degree+1] - knots[i+1])`, over the knot vector
+/// trimmed by one knot from each end. The subtraction is expressed as `P_{i+1} + P_i * -1.0` so
+/// that `T` only needs `Add` and scalar `Mul`, not `Sub`.
+fn differentiate_once<T>(control_points: &[T], knots: &[f64], degree: usize) -> (Vec<T>, Vec<f64>)
+where
+    T: Add<Output = T> + Mul<f64, Output = T> + Clone,
+{
+    let derivative_points: Vec<T> = (0..control_points.len() - 1)
+        .map(|i| {
+            let difference = control_points[i + 1].clone() + control_points[i].clone() * -1.0;
+            difference * (degree as f64 / (knots[i + degree + 1] - knots[i + 1]))
+        })
+        .collect();
+    let derivative_knots = knots[1..knots.len() - 1].to_vec();
+    (derivative_points, derivative_knots)
+}
+
+/// Evaluate the `order`-th derivative of the B-spline at the given parameter `x`, by applying
+/// the control-point differencing identity `order` times to obtain a lower-degree spline, then
+/// evaluating that spline at `x`. A degree-0 (piecewise-constant) spline has no further
+/// derivative, so once `order` exceeds `degree` the result is 0 everywhere, rather than
+/// differentiating past degree 0.
+pub fn b_spline_derivative<T>(
+    x: f64,
+    control_points: &[T],
+    knots: &[f64],
+    degree: usize,
+    order: usize,
+) -> T
+where
+    T: Add<Output = T> + Mul<f64, Output = T> + Clone,
+{
+    let mut points = control_points.to_vec();
+    let mut knots = knots.to_vec();
+    let mut degree = degree;
+    for _ in 0..order {
+        if degree == 0 {
+            return points[0].clone() * 0.0;
+        }
+        let (next_points, next_knots) = differentiate_once(&points, &knots, degree);
+        points = next_points;
+        knots = next_knots;
+        degree -= 1;
+    }
+    b_spline(x, &points, &knots, degree)
+}
+
+/// evaluate `b_spline` for every `x` in `xs`, four at a time, via explicit SIMD: the knot span
+/// search stays scalar per lane (spans can differ across lanes), but the triangular de Boor
+/// recurrence itself runs lane-parallel as `f64x4` arithmetic. Any trailing `xs` that don't fill
+/// a full lane of four are evaluated with the scalar `b_spline`.
+pub fn b_spline_batch(xs: &[f64], control_points: &[f64], knots: &[f64], degree: usize) -> Vec<f64> {
+    let mut results = Vec::with_capacity(xs.len());
+    let mut chunks = xs.chunks_exact(4);
+    for chunk in &mut chunks {
+        let mus: [usize; 4] =
+            std::array::from_fn(|lane| find_knot_span(chunk[lane], degree, control_points.len(), knots));
+
+        let mut n = vec![f64x4::splat(0.0); degree + 1];
+        n[0] = f64x4::splat(1.0);
+        let mut left = vec![f64x4::splat(0.0); degree + 1];
+        let mut right = vec![f64x4::splat(0.0); degree + 1];
+        for j in 1..=degree {
+            left[j] = f64x4::from_array(std::array::from_fn(|lane| {
+                chunk[lane] - knots[mus[lane] + 1 - j]
+            }));
+            right[j] = f64x4::from_array(std::array::from_fn(|lane| {
+                knots[mus[lane] + j] - chunk[lane]
+            }));
+            let mut saved = f64x4::splat(0.0);
+            for r in 0..j {
+                let denominator = right[r + 1] + left[j - r];
+                // mirror the zero-width-knot-interval guard in `nonzero_basis_functions`, lane-wise
+                let zero_denominator = denominator.simd_eq(f64x4::splat(0.0));
+                let temp = zero_denominator.select(f64x4::splat(0.0), n[r] / denominator);
+                n[r] = saved + right[r + 1] * temp;
+                saved = left[j - r] * temp;
+            }
+            n[j] = saved;
+        }
+
+        let mut acc = f64x4::splat(0.0);
+        for r in 0..=degree {
+            let cp = f64x4::from_array(std::array::from_fn(|lane| {
+                control_points[mus[lane] - degree + r]
+            }));
+            acc += cp * n[r];
+        }
+        results.extend_from_slice(acc.as_array());
+    }
+    for &x in chunks.remainder() {
+        results.push(b_spline(x, control_points, knots, degree));
+    }
+    results
+}
+
+/// fits a B-spline's control points to sampled `(x, y)` data in a least-squares sense, over a
+/// fixed knot vector and degree, by solving the normal equations `B^T B c = B^T y` for the
+/// coefficients `c`, where `B` is the `m x n` basis matrix (row per sample, column per basis
+/// function)
+pub struct BSplineBuilder {
+    knots: Vec<f64>,
+    degree: usize,
+    regularization: f64,
+}
+
+impl BSplineBuilder {
+    /// create a builder for the given knot vector and degree, with no regularization
+    pub fn new(knots: Vec<f64>, degree: usize) -> Self {
+        BSplineBuilder {
+            knots,
+            degree,
+            regularization: 0.0,
+        }
+    }
+
+    /// penalize the second difference of the fitted control points by `lambda`, to smooth over
+    /// noisy data
+    pub fn regularization(mut self, lambda: f64) -> Self {
+        self.regularization = lambda;
+        self
+    }
+
+    /// solve for the control points that best fit `(xs[k], ys[k])` in a least-squares sense
+    pub fn fit(&self, xs: &[f64], ys: &[f64]) -> Vec<f64> {
+        assert_eq!(xs.len(), ys.len(), "xs and ys must have the same length");
+        let n = self.knots.len() - self.degree - 1; // number of basis functions / control points
+
+        let mut bt_b = vec![vec![0.0; n]; n];
+        let mut bt_y = vec![0.0; n];
+        for (&x, &y) in xs.iter().zip(ys.iter()) {
+            let mu = find_knot_span(x, self.degree, n, &self.knots);
+            let basis = nonzero_basis_functions(mu, self.degree, x, &self.knots);
+            let base = mu - self.degree;
+            for r in 0..=self.degree {
+                bt_y[base + r] += basis[r] * y;
+                for s in 0..=self.degree {
+                    bt_b[base + r][base + s] += basis[r] * basis[s];
+                }
+            }
+        }
+
+        if self.regularization > 0.0 {
+            // D is the (n - 2) x n second-difference operator; add lambda * D^T * D directly by
+            // accumulating each row's outer product, since D is sparse (three non-zeros per row)
+            for i in 0..n.saturating_sub(2) {
+                let row = [(i, 1.0), (i + 1, -2.0), (i + 2, 1.0)];
+                for &(c1, v1) in &row {
+                    for &(c2, v2) in &row {
+                        bt_b[c1][c2] += self.regularization * v1 * v2;
+                    }
+                }
+            }
+        }
+
+        solve_linear_system(bt_b, bt_y)
+    }
+}
+
+/// solve the dense linear system `a * x = b` via Gaussian elimination with partial pivoting
+fn solve_linear_system(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Vec<f64> {
+    let n = b.len();
+    for col in 0..n {
+        let mut pivot = col;
+        for row in (col + 1)..n {
+            if a[row][col].abs() > a[pivot][col].abs() {
+                pivot = row;
+            }
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+
+        let diag = a[col][col];
+        let pivot_row = a[col][col..].to_vec();
+        for row in (col + 1)..n {
+            let factor = a[row][col] / diag;
+            for (entry, pivot_entry) in a[row][col..].iter_mut().zip(pivot_row.iter()) {
+                *entry -= factor * pivot_entry;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let mut sum = b[row];
+        for k in (row + 1)..n {
+            sum -= a[row][k] * x[k];
+        }
+        x[row] = sum / a[row][row];
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derivative_of_linear_spline_is_constant_slope() {
+        // degree-1 spline through (0,0), (1,1), (2,0) has slope 1 on [0,1) and -1 on [1,2)
+        let knots = vec![0.0, 0.0, 1.0, 2.0, 2.0];
+        let control_points = vec![0.0, 1.0, 0.0];
+        assert_eq!(b_spline_derivative(0.5, &control_points, &knots, 1, 1), 1.0);
+        assert_eq!(b_spline_derivative(1.5, &control_points, &knots, 1, 1), -1.0);
+    }
+
+    #[test]
+    fn derivative_order_past_degree_is_zero() {
+        let knots = vec![0.0, 0.0, 1.0, 2.0, 2.0];
+        let control_points = vec![0.0, 1.0, 0.0];
+        assert_eq!(b_spline_derivative(0.5, &control_points, &knots, 1, 1), 1.0);
+        assert_eq!(b_spline_derivative(0.5, &control_points, &knots, 1, 2), 0.0);
+    }
+
+    #[test]
+    fn derivative_order_beyond_degree_does_not_panic() {
+        let knots = vec![0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0];
+        let control_points = vec![0.0, 1.0, 2.0, 3.0];
+        // order (4) exceeds degree (3): must saturate to 0 instead of underflowing `degree`
+        assert_eq!(
+            b_spline_derivative(0.5, &control_points, &knots, 3, 4),
+            0.0
+        );
+    }
+
+    #[test]
+    fn clamped_spline_reaches_final_control_point_at_endpoint() {
+        // clamped knots make the curve interpolate its first and last control points
+        let knots = clamped_knot_vector(4, 2);
+        let control_points = vec![1.0, 2.0, 3.0, 7.0];
+        let last_knot = *knots.last().unwrap();
+        assert_eq!(
+            b_spline(last_knot, &control_points, &knots, 2),
+            *control_points.last().unwrap()
+        );
+    }
+
+    #[test]
+    fn builder_fits_exactly_sampled_linear_points() {
+        // degree-1 spline over [0, 3]; sampling exactly at the control points should round-trip
+        let knots = clamped_knot_vector(4, 1);
+        let xs = vec![0.0, 1.0, 2.0, 3.0];
+        let ys = vec![0.0, 2.0, 4.0, 6.0];
+        let fitted = BSplineBuilder::new(knots, 1).fit(&xs, &ys);
+        for (got, want) in fitted.iter().zip(ys.iter()) {
+            assert!((got - want).abs() < 1e-9, "got {got}, want {want}");
+        }
+    }
+
+    #[test]
+    fn rational_with_equal_weights_matches_b_spline() {
+        let knots = clamped_knot_vector(4, 2);
+        let control_points = vec![1.0, 2.0, 3.0, 7.0];
+        let weights = vec![1.0, 1.0, 1.0, 1.0];
+        let x = 0.75;
+        assert_eq!(
+            b_spline_rational(x, &control_points, &weights, &knots, 2),
+            b_spline(x, &control_points, &knots, 2)
+        );
+    }
+
+    #[test]
+    fn rational_pulls_toward_more_heavily_weighted_control_point() {
+        let knots = clamped_knot_vector(4, 2);
+        let control_points = vec![0.0, 10.0, 10.0, 20.0];
+        let x = 0.5;
+        let equal_weights = vec![1.0, 1.0, 1.0, 1.0];
+        let heavy_weights = vec![1.0, 10.0, 1.0, 1.0];
+        let equal = b_spline_rational(x, &control_points, &equal_weights, &knots, 2);
+        let heavy = b_spline_rational(x, &control_points, &heavy_weights, &knots, 2);
+        assert!(heavy > equal, "heavy {heavy} should exceed equal {equal}");
+    }
+
+    #[test]
+    fn batch_matches_scalar_for_interior_points() {
+        let degree = 3;
+        let knots: Vec<f64> = (0..20).map(|x| x as f64).collect();
+        let control_points: Vec<f64> = (0..16).map(|i| i as f64).collect();
+        let xs: Vec<f64> = (0..100).map(|x| x as f64 / 10.0).collect();
+
+        let batched = b_spline_batch(&xs, &control_points, &knots, degree);
+        for (&x, &got) in xs.iter().zip(batched.iter()) {
+            let want = b_spline(x, &control_points, &knots, degree);
+            assert!((got - want).abs() < 1e-9, "x={x}: got {got}, want {want}");
+        }
+    }
+
+    #[test]
+    fn batch_matches_scalar_at_clamped_endpoint() {
+        let degree = 2;
+        let knots = clamped_knot_vector(4, degree);
+        let control_points = vec![1.0, 2.0, 3.0, 7.0];
+        let last_knot = *knots.last().unwrap();
+        let xs = vec![0.0, 0.3, 0.9, last_knot];
+
+        let batched = b_spline_batch(&xs, &control_points, &knots, degree);
+        for (&x, &got) in xs.iter().zip(batched.iter()) {
+            let want = b_spline(x, &control_points, &knots, degree);
+            assert!((got - want).abs() < 1e-9, "x={x}: got {got}, want {want}");
+        }
+        assert_eq!(*batched.last().unwrap(), *control_points.last().unwrap());
+    }
+}